@@ -1,12 +1,14 @@
-use nextpnr::{Context, Nets};
+use nextpnr::{catch_panic, Context, Nets};
 
 #[no_mangle]
-pub extern "C" fn rust_example_printnets(ctx: &mut Context) {
-    let nets = Nets::new(ctx);
-    let nets_vec = nets.to_vec();
+pub extern "C-unwind" fn rust_example_printnets(ctx: &mut Context) {
+    catch_panic(|| {
+        let nets = Nets::new(ctx);
+        let nets_vec = nets.to_vec();
 
-    println!("Nets in design:");
-    for (&name, _net) in nets_vec {
-        println!("  {}", ctx.name_of(name).to_str().unwrap());
-    }
+        println!("Nets in design:");
+        for (&name, _net) in nets_vec {
+            println!("  {}", ctx.name_of(name).to_string_lossy());
+        }
+    });
 }
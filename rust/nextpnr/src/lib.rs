@@ -1,7 +1,12 @@
 use core::slice;
-use std::{collections::HashMap, ffi::CStr, marker::PhantomData, sync::Mutex};
-
-use libc::c_char;
+use std::{
+    collections::HashMap,
+    ffi::{c_char, c_int, CStr, CString},
+    marker::PhantomData,
+    panic::{self, UnwindSafe},
+    ptr::NonNull,
+    sync::Mutex,
+};
 
 #[derive(Clone, Copy)]
 #[repr(C)]
@@ -21,8 +26,10 @@ pub struct CellInfo {
 }
 
 impl CellInfo {
-    pub fn location(&self) -> Loc {
-        unsafe { npnr_cellinfo_get_location(self) }
+    /// Get the location of the cell, or `None` if it is not placed.
+    pub fn location(&self) -> Option<Loc> {
+        let mut loc = Loc { x: 0, y: 0, z: 0 };
+        unsafe { npnr_cellinfo_get_location(self, &mut loc) }.then_some(loc)
     }
 }
 
@@ -43,6 +50,19 @@ impl NetInfo {
     pub fn index(&self) -> NetIndex {
         unsafe { npnr_netinfo_udata(self) }
     }
+
+    /// Get a string attribute of the net, or `None` if it is unset or not a string.
+    pub fn attr_str(&self, name: IdString) -> Option<&CStr> {
+        let s = unsafe { npnr_netinfo_get_attr_str(self, name) };
+        (!s.is_null()).then(|| unsafe { CStr::from_ptr(s) })
+    }
+
+    /// Get an integer attribute of the net, or `None` if it is unset, a string, has undefined (x/z) bits, or is wider
+    /// than 64 bits.
+    pub fn attr_int(&self, name: IdString) -> Option<i64> {
+        let mut value = 0;
+        unsafe { npnr_netinfo_get_attr_int(self, name, &mut value) }.then_some(value)
+    }
 }
 
 #[repr(transparent)]
@@ -69,7 +89,7 @@ impl PortRef {
 
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
 #[repr(transparent)]
-pub struct IdString(libc::c_int);
+pub struct IdString(c_int);
 
 /// A type representing a bel name.
 #[derive(Clone, Copy, PartialEq, Eq)]
@@ -119,9 +139,9 @@ impl WireId {
 #[repr(C)]
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Loc {
-    pub x: libc::c_int,
-    pub y: libc::c_int,
-    pub z: libc::c_int,
+    pub x: c_int,
+    pub y: c_int,
+    pub z: c_int,
 }
 
 impl From<(i32, i32)> for Loc {
@@ -134,6 +154,26 @@ impl From<(i32, i32)> for Loc {
     }
 }
 
+/// The dimensions of a context's grid, as returned by [`Context::grid_dims`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GridDims {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl GridDims {
+    /// Check if a location is on the grid. Architectures must not report bel or pip locations
+    /// outside it, so `false` indicates a bug in the architecture.
+    pub fn contains(&self, loc: Loc) -> bool {
+        (0..self.x).contains(&loc.x) && (0..self.y).contains(&loc.y)
+    }
+
+    /// Return the location if it is on the grid, or `None` if it is not. See [`GridDims::contains`].
+    pub fn check(&self, loc: Loc) -> Option<Loc> {
+        self.contains(loc).then_some(loc)
+    }
+}
+
 static RINGBUFFER_MUTEX: Mutex<()> = Mutex::new(());
 static ARCH_MUTEX: Mutex<()> = Mutex::new(());
 
@@ -153,6 +193,14 @@ impl Context {
         unsafe { npnr_context_get_grid_dim_y(self) }
     }
 
+    /// Get both grid dimensions, for checking many locations without an FFI call each.
+    pub fn grid_dims(&self) -> GridDims {
+        GridDims {
+            x: self.grid_dim_x(),
+            y: self.grid_dim_y(),
+        }
+    }
+
     /// Bind a given bel to a given cell with the given strength.
     pub fn bind_bel(&mut self, bel: BelId, cell: &mut CellInfo, strength: PlaceStrength) {
         let _lock = ARCH_MUTEX.lock().unwrap();
@@ -182,6 +230,19 @@ impl Context {
         unsafe { npnr_context_unbind_wire(self, wire) }
     }
 
+    /// Returns true if the wire is available. A wire can be unavailable because it is bound, or because it is exclusive to some other resource that is bound.
+    pub fn check_wire_avail(&self, wire: WireId) -> bool {
+        unsafe { npnr_context_check_wire_avail(self, wire) }
+    }
+
+    /// Check the availability of a slice of wires in a single FFI call. See [`Context::check_wire_avail`].
+    pub fn check_wires_avail(&self, wires: &[WireId]) -> Vec<bool> {
+        let mut avail = vec![false; wires.len()];
+        let len = wires.len() as u64;
+        unsafe { npnr_context_check_wires_avail(self, wires.as_ptr(), avail.as_mut_ptr(), len) };
+        avail
+    }
+
     /// Bind a pip to a net. This also binds the destination wire of that pip.
     pub fn bind_pip(&mut self, pip: PipId, net: &mut NetInfo, strength: PlaceStrength) {
         let _lock = ARCH_MUTEX.lock().unwrap();
@@ -194,14 +255,16 @@ impl Context {
         unsafe { npnr_context_unbind_pip(self, pip) }
     }
 
-    /// Get the source wire for a pip.
-    pub fn pip_src_wire(&self, pip: PipId) -> WireId {
-        unsafe { npnr_context_get_pip_src_wire(self, pip) }
+    /// Get the source wire for a pip, or `None` if the pip has no source wire.
+    pub fn pip_src_wire(&self, pip: PipId) -> Option<WireId> {
+        let wire = unsafe { npnr_context_get_pip_src_wire(self, pip) };
+        (!wire.is_null()).then_some(wire)
     }
 
-    /// Get the destination wire for a pip.
-    pub fn pip_dst_wire(&self, pip: PipId) -> WireId {
-        unsafe { npnr_context_get_pip_dst_wire(self, pip) }
+    /// Get the destination wire for a pip, or `None` if the pip has no destination wire.
+    pub fn pip_dst_wire(&self, pip: PipId) -> Option<WireId> {
+        let wire = unsafe { npnr_context_get_pip_dst_wire(self, pip) };
+        (!wire.is_null()).then_some(wire)
     }
 
     // TODO: Should this be a Duration? Does that even make sense?
@@ -221,8 +284,10 @@ impl Context {
         unsafe { npnr_context_delay_epsilon(self) }
     }
 
-    pub fn source_wire(&self, net: &NetInfo) -> WireId {
-        unsafe { npnr_context_get_netinfo_source_wire(self, net) }
+    /// Get the wire driving a net, or `None` if the net has no driver bound to a bel.
+    pub fn source_wire(&self, net: &NetInfo) -> Option<WireId> {
+        let wire = unsafe { npnr_context_get_netinfo_source_wire(self, net) };
+        (!wire.is_null()).then_some(wire)
     }
 
     pub fn sink_wires(&self, net: &NetInfo, sink: &PortRef) -> Vec<WireId> {
@@ -251,7 +316,7 @@ impl Context {
         unsafe { std::slice::from_raw_parts(pips, len as usize) }
     }
 
-    pub fn get_downhill_pips(&self, wire: WireId) -> DownhillPipsIter {
+    pub fn get_downhill_pips(&self, wire: WireId) -> DownhillPipsIter<'_> {
         let iter = unsafe { npnr_context_get_pips_downhill(self, wire) };
         DownhillPipsIter {
             iter,
@@ -259,7 +324,7 @@ impl Context {
         }
     }
 
-    pub fn get_uphill_pips(&self, wire: WireId) -> UphillPipsIter {
+    pub fn get_uphill_pips(&self, wire: WireId) -> UphillPipsIter<'_> {
         let iter = unsafe { npnr_context_get_pips_uphill(self, wire) };
         UphillPipsIter {
             iter,
@@ -267,45 +332,64 @@ impl Context {
         }
     }
 
+    /// Get the location of a pip, without checking it is on the grid. See [`Context::pip_location_on`].
     pub fn pip_location(&self, pip: PipId) -> Loc {
         unsafe { npnr_context_get_pip_location(self, pip) }
     }
 
-    pub fn pip_direction(&self, pip: PipId) -> Loc {
-        let mut src = Loc{x: 0, y: 0, z: 0};
-        let mut dst = Loc{x: 0, y: 0, z: 0};
-
-        let mut pips = 0;
-        for pip in self.get_uphill_pips(self.pip_src_wire(pip)) {
-            let loc = self.pip_location(pip);
-            src.x += loc.x;
-            src.y += loc.y;
-            pips += 1;
-        }
-        if pips != 0 {
-            src.x /= pips;
-            src.y /= pips;
-        }
+    /// Get the location of a pip, or `None` if the architecture places it outside the grid.
+    ///
+    /// `dims` should come from [`Context::grid_dims`], read once by the caller, so this is still a
+    /// single FFI call.
+    pub fn pip_location_on(&self, pip: PipId, dims: &GridDims) -> Option<Loc> {
+        dims.check(self.pip_location(pip))
+    }
 
-        let mut pips = 0;
-        for pip in self.get_downhill_pips(self.pip_dst_wire(pip)) {
-            let loc = self.pip_location(pip);
-            dst.x += loc.x;
-            dst.y += loc.y;
-            pips += 1;
-        }
-        if pips != 0 {
-            dst.x /= pips;
-            dst.y /= pips;
-        }
+    /// Get the locations of a slice of pips in a single FFI call, without checking they are on the
+    /// grid. See [`Context::pip_locations_on`].
+    pub fn pip_locations(&self, pips: &[PipId]) -> Vec<Loc> {
+        let mut locs = vec![Loc { x: 0, y: 0, z: 0 }; pips.len()];
+        let len = pips.len() as u64;
+        unsafe { npnr_context_get_pip_locations(self, pips.as_ptr(), locs.as_mut_ptr(), len) };
+        locs
+    }
 
-        Loc{x: dst.x - src.x, y: dst.y - src.y, z: 0}
+    /// Get the locations of a slice of pips in a single FFI call, with `None` for each pip the
+    /// architecture places outside the grid. See [`Context::pip_location_on`].
+    pub fn pip_locations_on(&self, pips: &[PipId], dims: &GridDims) -> Vec<Option<Loc>> {
+        let locs = self.pip_locations(pips);
+        locs.into_iter().map(|loc| dims.check(loc)).collect()
+    }
+
+    /// Estimate the direction a pip drives in, as the difference between the average location
+    /// of the pips driving its source wire and the average location of the pips its destination
+    /// wire drives.
+    pub fn pip_direction(&self, pip: PipId) -> Loc {
+        unsafe { npnr_context_get_pip_direction(self, pip) }
+    }
+
+    /// Get the directions of a slice of pips in a single FFI call. See [`Context::pip_direction`].
+    pub fn pip_directions(&self, pips: &[PipId]) -> Vec<Loc> {
+        let mut dirs = vec![Loc { x: 0, y: 0, z: 0 }; pips.len()];
+        let len = pips.len() as u64;
+        unsafe { npnr_context_get_pip_directions(self, pips.as_ptr(), dirs.as_mut_ptr(), len) };
+        dirs
     }
 
     pub fn pip_avail_for_net(&self, pip: PipId, net: &mut NetInfo) -> bool {
         unsafe { npnr_context_check_pip_avail_for_net(self, pip, net) }
     }
 
+    /// Returns true if the pip lies inside a region the net's driver or users are constrained to, or if none of them
+    /// are.
+    ///
+    /// A pip is inside a region if its tile (`x` and `y`; `z` is ignored) contains a bel of the region, so pips in
+    /// tiles without bels, such as pure routing tiles, are never inside a region. This looks at every user of the net
+    /// and every bel in the pip's tile, so it is not cheap on high-fanout nets.
+    pub fn pip_in_net_region(&self, pip: PipId, net: &NetInfo) -> bool {
+        unsafe { npnr_context_check_pip_in_net_region(self, pip, net) }
+    }
+
     pub fn check(&self) {
         unsafe { npnr_context_check(self) }
     }
@@ -339,16 +423,20 @@ impl Context {
     }
 }
 
-extern "C" {
+// nextpnr reports errors (including `log_error` and failed assertions) by throwing C++ exceptions,
+// which must never reach Rust frames. The shims in rust.cc catch them and unwind the Rust stack with
+// a Rust panic instead (see `npnr_rust_unwind`), so these functions use the `C-unwind` ABI.
+extern "C-unwind" {
     pub fn npnr_log_info(format: *const c_char);
     pub fn npnr_log_error(format: *const c_char);
+    fn npnr_rust_panicked(msg: *const c_char);
 
     fn npnr_belid_null() -> BelId;
     fn npnr_wireid_null() -> WireId;
     fn npnr_pipid_null() -> PipId;
 
-    fn npnr_context_get_grid_dim_x(ctx: &Context) -> libc::c_int;
-    fn npnr_context_get_grid_dim_y(ctx: &Context) -> libc::c_int;
+    fn npnr_context_get_grid_dim_x(ctx: &Context) -> c_int;
+    fn npnr_context_get_grid_dim_y(ctx: &Context) -> c_int;
     fn npnr_context_bind_bel(
         ctx: &mut Context,
         bel: BelId,
@@ -364,6 +452,13 @@ extern "C" {
         strength: PlaceStrength,
     );
     fn npnr_context_unbind_wire(ctx: &mut Context, wire: WireId);
+    fn npnr_context_check_wire_avail(ctx: &Context, wire: WireId) -> bool;
+    fn npnr_context_check_wires_avail(
+        ctx: &Context,
+        wires: *const WireId,
+        avail: *mut bool,
+        len: u64,
+    );
     fn npnr_context_bind_pip(
         ctx: &mut Context,
         pip: PipId,
@@ -380,18 +475,22 @@ extern "C" {
     fn npnr_context_get_wires_leak(ctx: &Context, wires: *mut *mut WireId) -> u64;
     fn npnr_context_get_pips_leak(ctx: &Context, pips: *mut *mut PipId) -> u64;
     fn npnr_context_get_pip_location(ctx: &Context, pip: PipId) -> Loc;
+    fn npnr_context_get_pip_locations(ctx: &Context, pips: *const PipId, locs: *mut Loc, len: u64);
+    fn npnr_context_get_pip_direction(ctx: &Context, pip: PipId) -> Loc;
+    fn npnr_context_get_pip_directions(ctx: &Context, pips: *const PipId, dirs: *mut Loc, len: u64);
     fn npnr_context_check_pip_avail_for_net(
         ctx: &Context,
         pip: PipId,
         net: &NetInfo,
     ) -> bool;
+    fn npnr_context_check_pip_in_net_region(ctx: &Context, pip: PipId, net: &NetInfo) -> bool;
 
     fn npnr_context_check(ctx: &Context);
     fn npnr_context_debug(ctx: &Context) -> bool;
     fn npnr_context_id(ctx: &Context, s: *const c_char) -> IdString;
-    fn npnr_context_name_of(ctx: &Context, s: IdString) -> *const libc::c_char;
-    fn npnr_context_name_of_pip(ctx: &Context, pip: PipId) -> *const libc::c_char;
-    fn npnr_context_name_of_wire(ctx: &Context, wire: WireId) -> *const libc::c_char;
+    fn npnr_context_name_of(ctx: &Context, s: IdString) -> *const c_char;
+    fn npnr_context_name_of_pip(ctx: &Context, pip: PipId) -> *const c_char;
+    fn npnr_context_name_of_wire(ctx: &Context, wire: WireId) -> *const c_char;
     fn npnr_context_verbose(ctx: &Context) -> bool;
 
     fn npnr_context_get_netinfo_source_wire(ctx: &Context, net: &NetInfo) -> WireId;
@@ -404,7 +503,7 @@ extern "C" {
 
     fn npnr_context_nets_leak(
         ctx: &Context,
-        names: *mut *mut libc::c_int,
+        names: *mut *mut c_int,
         nets: *mut *mut *mut NetInfo,
     ) -> u32;
     fn npnr_context_get_pips_downhill(ctx: &Context, wire: WireId) -> &mut RawDownhillIter;
@@ -415,11 +514,28 @@ extern "C" {
     fn npnr_netinfo_driver(net: &mut NetInfo) -> Option<&mut PortRef>;
     fn npnr_netinfo_users_leak(net: &NetInfo, users: *mut *mut *const PortRef) -> u32;
     fn npnr_netinfo_is_global(net: &NetInfo) -> bool;
+    fn npnr_netinfo_get_attr_str(net: &NetInfo, name: IdString) -> *const c_char;
+    fn npnr_netinfo_get_attr_int(net: &NetInfo, name: IdString, value: &mut i64) -> bool;
     fn npnr_netinfo_udata(net: &NetInfo) -> NetIndex;
     fn npnr_netinfo_udata_set(net: &mut NetInfo, value: NetIndex);
 
     fn npnr_portref_cell(port: &PortRef) -> Option<&CellInfo>;
-    fn npnr_cellinfo_get_location(info: &CellInfo) -> Loc;
+    fn npnr_cellinfo_get_location(info: &CellInfo, loc: &mut Loc) -> bool;
+
+    fn npnr_timing_analyser_new(ctx: &Context) -> NonNull<RawTimingAnalyser>;
+    fn npnr_timing_analyser_run(ctx: &Context, tmg: NonNull<RawTimingAnalyser>);
+    fn npnr_delete_timing_analyser(tmg: NonNull<RawTimingAnalyser>);
+    fn npnr_timing_analyser_get_criticality(
+        tmg: NonNull<RawTimingAnalyser>,
+        port: &PortRef,
+        crit: &mut f32,
+    ) -> bool;
+    fn npnr_timing_analyser_get_setup_slack(
+        ctx: &Context,
+        tmg: NonNull<RawTimingAnalyser>,
+        port: &PortRef,
+        slack: &mut f32,
+    ) -> bool;
 
     fn npnr_inc_downhill_iter(iter: &mut RawDownhillIter);
     fn npnr_deref_downhill_iter(iter: &mut RawDownhillIter) -> PipId;
@@ -442,12 +558,12 @@ impl<'a> Nets<'a> {
     ///
     /// Note that this leaks memory created by nextpnr; the intention is this is called once.
     pub fn new(ctx: &'a Context) -> Nets<'a> {
-        let mut names: *mut libc::c_int = std::ptr::null_mut();
+        let mut names: *mut c_int = std::ptr::null_mut();
         let mut nets_ptr: *mut *mut NetInfo = std::ptr::null_mut();
         let size = unsafe {
             npnr_context_nets_leak(
                 ctx,
-                &mut names as *mut *mut libc::c_int,
+                &mut names as *mut *mut c_int,
                 &mut nets_ptr as *mut *mut *mut NetInfo,
             )
         };
@@ -495,12 +611,16 @@ impl<'a> Nets<'a> {
         self.nets.len() == 0
     }
 
-    pub fn name_from_index(&self, index: NetIndex) -> IdString {
-        self.index_to_net[index.0 as usize]
+    /// Find a net's name given its index, or `None` if the index is not from this store.
+    pub fn name_from_index(&self, index: NetIndex) -> Option<IdString> {
+        let index = usize::try_from(index.0).ok()?;
+        self.index_to_net.get(index).copied()
     }
 
-    pub fn net_from_index(&self, index: NetIndex) -> &NetInfo {
-        self.nets.get(&self.name_from_index(index)).unwrap()
+    /// Find a net given its index, or `None` if the index is not from this store.
+    pub fn net_from_index(&self, index: NetIndex) -> Option<&NetInfo> {
+        let name = self.name_from_index(index)?;
+        self.nets.get(&name).map(|net| &**net)
     }
 
     pub fn to_vec(&self) -> Vec<(&IdString, &&mut NetInfo)> {
@@ -592,17 +712,156 @@ impl<'a> Drop for UphillPipsIter<'a> {
     }
 }
 
+/// Panic payload used to unwind the Rust stack after a bindings call threw a C++ exception.
+struct CppException;
+
+/// Called by the shims in rust.cc after catching and storing a C++ exception.
+#[doc(hidden)]
+#[no_mangle]
+pub extern "C-unwind" fn npnr_rust_unwind() -> ! {
+    panic::resume_unwind(Box::new(CppException))
+}
+
+/// Run `f`, returning `None` if it panicked or a bindings call inside it threw a C++ exception.
+///
+/// Entry points called from C++ must wrap their body in this, and be called through `call_rust()`
+/// in rust.cc. A C++ exception thrown by a bindings call is stored on the C++ side, unwinds the
+/// Rust stack as a panic caught here, and is rethrown by `call_rust()` once the entry point has
+/// returned. Any other panic (including one a worker thread's join propagates) is reported as a
+/// nextpnr error in the same way.
+pub fn catch_panic<T>(f: impl FnOnce() -> T + UnwindSafe) -> Option<T> {
+    let payload = match panic::catch_unwind(f) {
+        Ok(value) => return Some(value),
+        Err(payload) => payload,
+    };
+    if !payload.is::<CppException>() {
+        let msg = if let Some(s) = payload.downcast_ref::<&str>() {
+            s
+        } else if let Some(s) = payload.downcast_ref::<String>() {
+            s.as_str()
+        } else {
+            "unknown panic"
+        };
+        let msg = CString::new(msg.replace('\0', "")).unwrap();
+        unsafe { npnr_rust_panicked(msg.as_ptr()) };
+    }
+    None
+}
+
+#[repr(C)]
+struct RawTimingAnalyser {
+    content: [u8; 0],
+}
+
+/// A handle to nextpnr's static timing analyser.
+///
+/// The analyser does not borrow the context, so routing can be bound between calls to
+/// [`TimingAnalyser::run`]. Every method taking a context must be given the one the analyser was
+/// created for.
+pub struct TimingAnalyser {
+    tmg: NonNull<RawTimingAnalyser>,
+}
+
+impl TimingAnalyser {
+    /// Create a timing analyser for a context and set it up with the current netlist.
+    pub fn new(ctx: &Context) -> Self {
+        let tmg = unsafe { npnr_timing_analyser_new(ctx) };
+        Self { tmg }
+    }
+
+    /// Re-run timing analysis, picking up the delays of any routing bound since the last run.
+    pub fn run(&mut self, ctx: &Context) {
+        unsafe { npnr_timing_analyser_run(ctx, self.tmg) }
+    }
+
+    /// Get the worst criticality of a net user, from 0 (most slack) to 1 (most critical).
+    ///
+    /// Returns `None` if the user has no cell or was added to the netlist after the analyser was created.
+    pub fn criticality(&self, sink: &PortRef) -> Option<f32> {
+        let mut crit = 0.0;
+        unsafe { npnr_timing_analyser_get_criticality(self.tmg, sink, &mut crit) }.then_some(crit)
+    }
+
+    /// Get the worst setup slack of a net user, in nanoseconds.
+    ///
+    /// Returns infinity if the user is unconstrained (outside any clock domain), and `None` if the user has no cell or
+    /// was added to the netlist after the analyser was created.
+    pub fn setup_slack(&self, ctx: &Context, sink: &PortRef) -> Option<f32> {
+        let mut slack = 0.0;
+        unsafe { npnr_timing_analyser_get_setup_slack(ctx, self.tmg, sink, &mut slack) }
+            .then_some(slack)
+    }
+}
+
+impl Drop for TimingAnalyser {
+    fn drop(&mut self) {
+        unsafe { npnr_delete_timing_analyser(self.tmg) };
+    }
+}
+
+#[macro_export]
 macro_rules! log_info {
     ($($t:tt)*) => {
         let s = std::ffi::CString::new(format!($($t)*)).unwrap();
-        unsafe { crate::npnr::npnr_log_info(s.as_ptr()); }
+        unsafe { $crate::npnr_log_info(s.as_ptr()); }
     };
 }
 
+#[macro_export]
 macro_rules! log_error {
     ($($t:tt)*) => {
         let s = std::ffi::CString::new(format!($($t)*)).unwrap();
-        unsafe { crate::npnr::npnr_log_error(s.as_ptr()); }
+        unsafe { $crate::npnr_log_error(s.as_ptr()); }
     };
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_dims_contains() {
+        let dims = GridDims { x: 4, y: 2 };
+        assert!(dims.contains(Loc { x: 0, y: 0, z: 7 }));
+        assert!(dims.contains(Loc { x: 3, y: 1, z: 0 }));
+        assert!(!dims.contains(Loc { x: 4, y: 1, z: 0 }));
+        assert!(!dims.contains(Loc { x: 3, y: 2, z: 0 }));
+        assert!(!dims.contains(Loc { x: -1, y: 0, z: 0 }));
+        assert!(!dims.contains(Loc { x: 0, y: -1, z: 0 }));
+    }
+
+    #[test]
+    fn grid_dims_check() {
+        let dims = GridDims { x: 4, y: 2 };
+        let on = Loc { x: 3, y: 1, z: 2 };
+        assert_eq!(dims.check(on), Some(on));
+        assert_eq!(dims.check(Loc { x: 4, y: 0, z: 0 }), None);
+        assert_eq!(dims.check(Loc { x: 0, y: -1, z: 0 }), None);
+    }
+
+    #[test]
+    fn nets_index_lookup() {
+        let mut a = NetInfo { private: [] };
+        let mut b = NetInfo { private: [] };
+        let (a_ptr, b_ptr): (*const NetInfo, *const NetInfo) = (&a, &b);
+        let nets = Nets {
+            nets: HashMap::from([(IdString(10), &mut a), (IdString(20), &mut b)]),
+            users: HashMap::new(),
+            index_to_net: vec![IdString(10), IdString(20)],
+            _data: PhantomData,
+        };
+
+        assert!(nets.name_from_index(NetIndex(0)) == Some(IdString(10)));
+        assert!(nets.name_from_index(NetIndex(1)) == Some(IdString(20)));
+        assert!(nets.name_from_index(NetIndex(2)).is_none());
+        assert!(nets.name_from_index(NetIndex(-1)).is_none());
+
+        let net = nets.net_from_index(NetIndex(0)).unwrap();
+        assert!(std::ptr::eq(net, a_ptr));
+        let net = nets.net_from_index(NetIndex(1)).unwrap();
+        assert!(std::ptr::eq(net, b_ptr));
+        assert!(nets.net_from_index(NetIndex(2)).is_none());
+        assert!(nets.net_from_index(NetIndex(-1)).is_none());
+        assert!(nets.net_from_index(NetIndex(i32::MIN)).is_none());
+    }
+}
@@ -16,14 +16,58 @@
  *  OR IN CONNECTION WITH THE USE OR PERFORMANCE OF THIS SOFTWARE.
  */
 
+#include <algorithm>
 #include <array>
+#include <exception>
+#include <limits>
+#include <mutex>
 #include <numeric>
+#include <stdexcept>
 #include "log.h"
 #include "nextpnr.h"
+#include "timing.h"
+
+extern "C" [[noreturn]] void npnr_rust_unwind();
 
 namespace {
 USING_NEXTPNR_NAMESPACE;
 
+// C++ exceptions must not unwind into Rust frames: catch_unwind aborts on foreign exceptions. Instead, shims that can
+// throw store the exception here and unwind the Rust stack with a Rust panic (npnr_rust_unwind), and call_rust()
+// rethrows it once the Rust entry point has returned. The first exception wins, so one thrown on a Rust worker thread
+// is not lost.
+std::mutex rust_exception_mutex;
+std::exception_ptr rust_exception;
+
+void store_exception(std::exception_ptr e)
+{
+    std::lock_guard<std::mutex> lock(rust_exception_mutex);
+    if (!rust_exception)
+        rust_exception = e;
+}
+
+template <typename F> auto guard(F &&f) -> decltype(f())
+{
+    try {
+        return f();
+    } catch (...) {
+        store_exception(std::current_exception());
+    }
+    npnr_rust_unwind();
+}
+
+template <typename F> void call_rust(F &&f)
+{
+    f();
+    std::exception_ptr e;
+    {
+        std::lock_guard<std::mutex> lock(rust_exception_mutex);
+        std::swap(e, rust_exception);
+    }
+    if (e)
+        std::rethrow_exception(e);
+}
+
 template <typename T> static inline uint64_t wrap(const T &thing) noexcept
 {
     static_assert(sizeof(T) <= 8, "T is too big for FFI");
@@ -53,6 +97,38 @@ static inline BelId unwrap_bel(const uint64_t bel) noexcept { return unwrap<BelI
 static inline PipId unwrap_pip(const uint64_t pip) noexcept { return unwrap<PipId>(pip); }
 
 static inline WireId unwrap_wire(const uint64_t wire) noexcept { return unwrap<WireId>(wire); }
+
+Loc pip_direction(const Context *ctx, PipId pip)
+{
+    Loc src{0, 0, 0};
+    Loc dst{0, 0, 0};
+
+    auto pips = 0;
+    for (auto uphill : ctx->getPipsUphill(ctx->getPipSrcWire(pip))) {
+        auto loc = ctx->getPipLocation(uphill);
+        src.x += loc.x;
+        src.y += loc.y;
+        pips++;
+    }
+    if (pips != 0) {
+        src.x /= pips;
+        src.y /= pips;
+    }
+
+    pips = 0;
+    for (auto downhill : ctx->getPipsDownhill(ctx->getPipDstWire(pip))) {
+        auto loc = ctx->getPipLocation(downhill);
+        dst.x += loc.x;
+        dst.y += loc.y;
+        pips++;
+    }
+    if (pips != 0) {
+        dst.x /= pips;
+        dst.y /= pips;
+    }
+
+    return Loc{dst.x - src.x, dst.y - src.y, 0};
+}
 } // namespace
 
 using DownhillIter = decltype(Context(ArchArgs()).getPipsDownhill(WireId()).begin());
@@ -78,7 +154,7 @@ extern "C" {
 USING_NEXTPNR_NAMESPACE;
 
 void npnr_log_info(const char *string) { log_info("%s", string); }
-void npnr_log_error(const char *string) { log_error("%s", string); }
+void npnr_log_error(const char *string) { guard([&] { log_error("%s", string); }); }
 
 uint64_t npnr_belid_null() { return wrap(BelId()); }
 uint64_t npnr_wireid_null() { return wrap(WireId()); }
@@ -88,121 +164,197 @@ int npnr_context_get_grid_dim_x(const Context *ctx) { return ctx->getGridDimX();
 int npnr_context_get_grid_dim_y(const Context *ctx) { return ctx->getGridDimY(); }
 void npnr_context_bind_bel(Context *ctx, uint64_t bel, CellInfo *cell, PlaceStrength strength)
 {
-    return ctx->bindBel(unwrap_bel(bel), cell, strength);
+    guard([&] { ctx->bindBel(unwrap_bel(bel), cell, strength); });
+}
+void npnr_context_unbind_bel(Context *ctx, uint64_t bel) { guard([&] { ctx->unbindBel(unwrap_bel(bel)); }); }
+bool npnr_context_check_bel_avail(Context *ctx, uint64_t bel)
+{
+    return guard([&] { return ctx->checkBelAvail(unwrap_bel(bel)); });
 }
-void npnr_context_unbind_bel(Context *ctx, uint64_t bel) { return ctx->unbindBel(unwrap_bel(bel)); }
-bool npnr_context_check_bel_avail(Context *ctx, uint64_t bel) { return ctx->checkBelAvail(unwrap_bel(bel)); }
 void npnr_context_bind_wire(Context *ctx, uint64_t wire, NetInfo *net, PlaceStrength strength)
 {
-    ctx->bindWire(unwrap_wire(wire), net, strength);
+    guard([&] { ctx->bindWire(unwrap_wire(wire), net, strength); });
+}
+void npnr_context_unbind_wire(Context *ctx, uint64_t wire) { guard([&] { ctx->unbindWire(unwrap_wire(wire)); }); }
+bool npnr_context_check_wire_avail(const Context *ctx, uint64_t wire)
+{
+    return guard([&] { return ctx->checkWireAvail(unwrap_wire(wire)); });
+}
+void npnr_context_check_wires_avail(const Context *ctx, const uint64_t *wires, bool *avail, uint64_t len)
+{
+    guard([&] {
+        for (uint64_t i = 0; i < len; i++)
+            avail[i] = ctx->checkWireAvail(unwrap_wire(wires[i]));
+    });
 }
-void npnr_context_unbind_wire(Context *ctx, uint64_t wire) { ctx->unbindWire(unwrap_wire(wire)); }
 void npnr_context_bind_pip(Context *ctx, uint64_t pip, NetInfo *net, PlaceStrength strength)
 {
-    ctx->bindPip(unwrap_pip(pip), net, strength);
+    guard([&] { ctx->bindPip(unwrap_pip(pip), net, strength); });
 }
-void npnr_context_unbind_pip(Context *ctx, uint64_t pip) { ctx->unbindPip(unwrap_pip(pip)); }
+void npnr_context_unbind_pip(Context *ctx, uint64_t pip) { guard([&] { ctx->unbindPip(unwrap_pip(pip)); }); }
 uint64_t npnr_context_get_pip_src_wire(const Context *ctx, uint64_t pip)
 {
-    return wrap(ctx->getPipSrcWire(unwrap_pip(pip)));
+    return guard([&] { return wrap(ctx->getPipSrcWire(unwrap_pip(pip))); });
 }
 uint64_t npnr_context_get_pip_dst_wire(const Context *ctx, uint64_t pip)
 {
-    return wrap(ctx->getPipDstWire(unwrap_pip(pip)));
+    return guard([&] { return wrap(ctx->getPipDstWire(unwrap_pip(pip))); });
 }
 float npnr_context_estimate_delay(const Context *ctx, uint64_t src, uint64_t dst)
 {
-    return ctx->getDelayNS(ctx->estimateDelay(unwrap_wire(src), unwrap_wire(dst)));
+    return guard([&] { return ctx->getDelayNS(ctx->estimateDelay(unwrap_wire(src), unwrap_wire(dst))); });
 }
 float npnr_context_get_pip_delay(const Context *ctx, uint64_t pip)
 {
-    return ctx->getDelayNS(ctx->getPipDelay(unwrap_pip(pip)).maxDelay());
+    return guard([&] { return ctx->getDelayNS(ctx->getPipDelay(unwrap_pip(pip)).maxDelay()); });
 }
 float npnr_context_get_wire_delay(const Context *ctx, uint64_t wire)
 {
-    return ctx->getDelayNS(ctx->getWireDelay(unwrap_wire(wire)).maxDelay());
+    return guard([&] { return ctx->getDelayNS(ctx->getWireDelay(unwrap_wire(wire)).maxDelay()); });
 }
 float npnr_context_delay_epsilon(const Context *ctx) { return ctx->getDelayNS(ctx->getDelayEpsilon()); }
-Loc npnr_context_get_pip_location(const Context *ctx, uint64_t pip) { return ctx->getPipLocation(unwrap_pip(pip)); }
+Loc npnr_context_get_pip_location(const Context *ctx, uint64_t pip)
+{
+    return guard([&] { return ctx->getPipLocation(unwrap_pip(pip)); });
+}
+void npnr_context_get_pip_locations(const Context *ctx, const uint64_t *pips, Loc *locs, uint64_t len)
+{
+    guard([&] {
+        for (uint64_t i = 0; i < len; i++)
+            locs[i] = ctx->getPipLocation(unwrap_pip(pips[i]));
+    });
+}
+Loc npnr_context_get_pip_direction(const Context *ctx, uint64_t pip)
+{
+    return guard([&] { return pip_direction(ctx, unwrap_pip(pip)); });
+}
+void npnr_context_get_pip_directions(const Context *ctx, const uint64_t *pips, Loc *dirs, uint64_t len)
+{
+    guard([&] {
+        for (uint64_t i = 0; i < len; i++)
+            dirs[i] = pip_direction(ctx, unwrap_pip(pips[i]));
+    });
+}
 bool npnr_context_check_pip_avail_for_net(const Context *ctx, uint64_t pip, NetInfo *net)
 {
-    return ctx->checkPipAvailForNet(unwrap_pip(pip), net);
+    return guard([&] { return ctx->checkPipAvailForNet(unwrap_pip(pip), net); });
+}
+bool npnr_context_check_pip_in_net_region(const Context *ctx, uint64_t pip, const NetInfo *net)
+{
+    return guard([&] {
+        // Region constraints apply to cells, so collect the regions of the net's driver and users.
+        std::vector<const Region *> regions;
+        auto add_region = [&](const CellInfo *cell) {
+            if (cell == nullptr || cell->region == nullptr || !cell->region->constr_bels)
+                return;
+            if (std::find(regions.begin(), regions.end(), cell->region) == regions.end())
+                regions.push_back(cell->region);
+        };
+        add_region(net->driver.cell);
+        for (auto &usr : net->users)
+            add_region(usr.cell);
+        if (regions.empty())
+            return true;
+
+        auto loc = ctx->getPipLocation(unwrap_pip(pip));
+        for (auto bel : ctx->getBelsByTile(loc.x, loc.y))
+            for (auto region : regions)
+                if (region->bels.count(bel))
+                    return true;
+        return false;
+    });
 }
 
 uint64_t npnr_context_get_pips_leak(const Context *ctx, uint64_t **const pips)
 {
-    const auto ctx_pips{ctx->getPips()};
-    const auto size{std::accumulate(ctx_pips.begin(), ctx_pips.end(), /*initial value*/ size_t{},
-                                    [](size_t value, const auto & /*pip*/) { return value + 1U; })};
-    *pips = new uint64_t[size];
-    auto idx = 0;
-    for (const auto &pip : ctx_pips) {
-        (*pips)[idx] = wrap(pip);
-        idx++;
-    }
-    // Yes, by never deleting pip_vec, we leak memory.
-    return size;
+    return guard([&] {
+        const auto ctx_pips{ctx->getPips()};
+        const auto size{std::accumulate(ctx_pips.begin(), ctx_pips.end(), /*initial value*/ size_t{},
+                                        [](size_t value, const auto & /*pip*/) { return value + 1U; })};
+        *pips = new uint64_t[size];
+        auto idx = 0;
+        for (const auto &pip : ctx_pips) {
+            (*pips)[idx] = wrap(pip);
+            idx++;
+        }
+        // Yes, by never deleting pip_vec, we leak memory.
+        return size;
+    });
 }
 
 uint64_t npnr_context_get_wires_leak(const Context *ctx, uint64_t **const wires)
 {
-    const auto ctx_wires{ctx->getWires()};
-    const auto size{std::accumulate(ctx_wires.begin(), ctx_wires.end(), /*initial value*/ size_t{},
-                                    [](size_t value, const auto & /*wire*/) { return value + 1U; })};
-    *wires = new uint64_t[size];
-    auto idx = 0;
-    for (const auto &wire : ctx_wires) {
-        (*wires)[idx] = wrap(wire);
-        idx++;
-    }
-    // Yes, by never deleting wires, we leak memory.
-    return size;
+    return guard([&] {
+        const auto ctx_wires{ctx->getWires()};
+        const auto size{std::accumulate(ctx_wires.begin(), ctx_wires.end(), /*initial value*/ size_t{},
+                                        [](size_t value, const auto & /*wire*/) { return value + 1U; })};
+        *wires = new uint64_t[size];
+        auto idx = 0;
+        for (const auto &wire : ctx_wires) {
+            (*wires)[idx] = wrap(wire);
+            idx++;
+        }
+        // Yes, by never deleting wires, we leak memory.
+        return size;
+    });
 }
 
-void npnr_context_check(const Context *ctx) { ctx->check(); }
+void npnr_context_check(const Context *ctx) { guard([&] { ctx->check(); }); }
 bool npnr_context_debug(const Context *ctx) { return ctx->debug; }
-int npnr_context_id(const Context *ctx, const char *str) { return ctx->id(str).hash(); }
-const char *npnr_context_name_of(const Context *ctx, IdString str) { return ctx->nameOf(str); }
-const char *npnr_context_name_of_pip(const Context *ctx, uint64_t pip) { return ctx->nameOfPip(unwrap_pip(pip)); }
-const char *npnr_context_name_of_wire(const Context *ctx, uint64_t wire) { return ctx->nameOfWire(unwrap_wire(wire)); }
+int npnr_context_id(const Context *ctx, const char *str) { return guard([&] { return ctx->id(str).hash(); }); }
+const char *npnr_context_name_of(const Context *ctx, IdString str) { return guard([&] { return ctx->nameOf(str); }); }
+const char *npnr_context_name_of_pip(const Context *ctx, uint64_t pip)
+{
+    return guard([&] { return ctx->nameOfPip(unwrap_pip(pip)); });
+}
+const char *npnr_context_name_of_wire(const Context *ctx, uint64_t wire)
+{
+    return guard([&] { return ctx->nameOfWire(unwrap_wire(wire)); });
+}
 bool npnr_context_verbose(const Context *ctx) { return ctx->verbose; }
 
 uint64_t npnr_context_get_netinfo_source_wire(const Context *ctx, const NetInfo *net)
 {
-    return wrap(ctx->getNetinfoSourceWire(net));
+    return guard([&] { return wrap(ctx->getNetinfoSourceWire(net)); });
 }
 uint64_t npnr_context_get_netinfo_sink_wire(const Context *ctx, const NetInfo *net, const PortRef *sink, uint32_t n)
 {
-    return wrap(ctx->getNetinfoSinkWire(net, *sink, n));
+    return guard([&] { return wrap(ctx->getNetinfoSinkWire(net, *sink, n)); });
 }
 
 uint32_t npnr_context_nets_leak(const Context *ctx, int **names, NetInfo ***nets)
 {
-    auto size = ctx->nets.size();
-    *names = new int[size];
-    *nets = new NetInfo *[size];
-    auto idx = 0;
-    for (auto &item : ctx->nets) {
-        (*names)[idx] = item.first.index;
-        (*nets)[idx] = item.second.get();
-        idx++;
-    }
-    // Yes, by never deleting `names` and `nets` we leak memory.
-    return size;
+    return guard([&] {
+        auto size = ctx->nets.size();
+        *names = new int[size];
+        *nets = new NetInfo *[size];
+        auto idx = 0;
+        for (auto &item : ctx->nets) {
+            (*names)[idx] = item.first.index;
+            (*nets)[idx] = item.second.get();
+            idx++;
+        }
+        // Yes, by never deleting `names` and `nets` we leak memory.
+        return size;
+    });
 }
 
 DownhillIterWrapper *npnr_context_get_pips_downhill(Context *ctx, uint64_t wire_id)
 {
-    auto wire = unwrap_wire(wire_id);
-    auto range = ctx->getPipsDownhill(wire);
-    return new DownhillIterWrapper(range.begin(), range.end());
+    return guard([&] {
+        auto wire = unwrap_wire(wire_id);
+        auto range = ctx->getPipsDownhill(wire);
+        return new DownhillIterWrapper(range.begin(), range.end());
+    });
 }
 void npnr_delete_downhill_iter(DownhillIterWrapper *iter) { delete iter; }
 UphillIterWrapper *npnr_context_get_pips_uphill(Context *ctx, uint64_t wire_id)
 {
-    auto wire = unwrap_wire(wire_id);
-    auto range = ctx->getPipsUphill(wire);
-    return new UphillIterWrapper(range.begin(), range.end());
+    return guard([&] {
+        auto wire = unwrap_wire(wire_id);
+        auto range = ctx->getPipsUphill(wire);
+        return new UphillIterWrapper(range.begin(), range.end());
+    });
 }
 void npnr_delete_uphill_iter(UphillIterWrapper *iter) { delete iter; }
 
@@ -216,15 +368,17 @@ PortRef *npnr_netinfo_driver(NetInfo *net)
 
 uint32_t npnr_netinfo_users_leak(const NetInfo *net, const PortRef ***users)
 {
-    auto size = net->users.entries();
-    *users = new const PortRef *[size];
-    auto idx = 0;
-    for (auto &item : net->users) {
-        (*users)[idx] = &item;
-        idx++;
-    }
-    // Yes, by not freeing `users`, we leak memory.
-    return size;
+    return guard([&] {
+        auto size = net->users.entries();
+        *users = new const PortRef *[size];
+        auto idx = 0;
+        for (auto &item : net->users) {
+            (*users)[idx] = &item;
+            idx++;
+        }
+        // Yes, by not freeing `users`, we leak memory.
+        return size;
+    });
 }
 
 #ifdef ARCH_ECP5
@@ -233,24 +387,104 @@ bool npnr_netinfo_is_global(NetInfo *net) { return net->is_global; }
 bool npnr_netinfo_is_global(NetInfo * /*net*/) { return false; }
 #endif
 
+const char *npnr_netinfo_get_attr_str(const NetInfo *net, IdString name)
+{
+    auto found = net->attrs.find(name);
+    if (found == net->attrs.end() || !found->second.is_string)
+        return nullptr;
+    return found->second.c_str();
+}
+bool npnr_netinfo_get_attr_int(const NetInfo *net, IdString name, int64_t *value)
+{
+    auto found = net->attrs.find(name);
+    if (found == net->attrs.end() || found->second.is_string || !found->second.is_fully_def() ||
+        found->second.str.size() > 64)
+        return false;
+    *value = found->second.as_int64();
+    return true;
+}
+
 int32_t npnr_netinfo_udata(NetInfo *net) { return net->udata; }
 void npnr_netinfo_udata_set(NetInfo *net, int32_t value) { net->udata = value; }
 
 CellInfo *npnr_portref_cell(const PortRef *port) { return port->cell; }
-Loc npnr_cellinfo_get_location(const CellInfo *info) { return info->getLocation(); }
+bool npnr_cellinfo_get_location(const CellInfo *info, Loc *loc)
+{
+    return guard([&] {
+        if (!info->pseudo_cell && info->bel == BelId())
+            return false;
+        *loc = info->getLocation();
+        return true;
+    });
+}
 
-void npnr_inc_downhill_iter(DownhillIterWrapper *iter) { ++iter->current; }
-uint64_t npnr_deref_downhill_iter(DownhillIterWrapper *iter) { return wrap(*iter->current); }
+TimingAnalyser *npnr_timing_analyser_new(Context *ctx)
+{
+    return guard([&] {
+        auto tmg = new TimingAnalyser(ctx);
+        tmg->setup();
+        return tmg;
+    });
+}
+void npnr_timing_analyser_run(const Context * /*ctx*/, TimingAnalyser *tmg) { guard([&] { tmg->run(); }); }
+void npnr_delete_timing_analyser(TimingAnalyser *tmg) { delete tmg; }
+bool npnr_timing_analyser_get_criticality(const TimingAnalyser *tmg, const PortRef *port, float *crit)
+{
+    if (port->cell == nullptr)
+        return false;
+    try {
+        *crit = tmg->get_criticality(CellPortKey(*port));
+    } catch (const std::out_of_range &) {
+        // The port was added after the analyser was set up.
+        return false;
+    }
+    return true;
+}
+bool npnr_timing_analyser_get_setup_slack(const Context *ctx, const TimingAnalyser *tmg, const PortRef *port,
+                                          float *slack)
+{
+    if (port->cell == nullptr)
+        return false;
+    float raw_slack;
+    try {
+        raw_slack = tmg->get_setup_slack(CellPortKey(*port));
+    } catch (const std::out_of_range &) {
+        // The port was added after the analyser was set up.
+        return false;
+    }
+    // Ports outside any clock domain keep the initial slack of numeric_limits<delay_t>::max(), which get_setup_slack()
+    // may round up past the range of an integer delay_t, so check for it before converting back.
+    if (raw_slack >= float(std::numeric_limits<delay_t>::max()))
+        *slack = std::numeric_limits<float>::infinity();
+    else
+        *slack = ctx->getDelayNS(delay_t(raw_slack));
+    return true;
+}
+
+void npnr_inc_downhill_iter(DownhillIterWrapper *iter) { guard([&] { ++iter->current; }); }
+uint64_t npnr_deref_downhill_iter(DownhillIterWrapper *iter) { return guard([&] { return wrap(*iter->current); }); }
 bool npnr_is_downhill_iter_done(DownhillIterWrapper *iter) { return !(iter->current != iter->end); }
-void npnr_inc_uphill_iter(UphillIterWrapper *iter) { ++iter->current; }
-uint64_t npnr_deref_uphill_iter(UphillIterWrapper *iter) { return wrap(*iter->current); }
+void npnr_inc_uphill_iter(UphillIterWrapper *iter) { guard([&] { ++iter->current; }); }
+uint64_t npnr_deref_uphill_iter(UphillIterWrapper *iter) { return guard([&] { return wrap(*iter->current); }); }
 bool npnr_is_uphill_iter_done(UphillIterWrapper *iter) { return !(iter->current != iter->end); }
 
+void npnr_rust_panicked(const char *msg)
+{
+    try {
+        log_error("Rust code panicked: %s\n", msg);
+    } catch (...) {
+        store_exception(std::current_exception());
+    }
+}
+
 void rust_example_printnets(Context *ctx);
 }
 
 NEXTPNR_NAMESPACE_BEGIN
 
-void example_printnets(Context *ctx) { rust_example_printnets(ctx); }
+void example_printnets(Context *ctx)
+{
+    call_rust([&] { rust_example_printnets(ctx); });
+}
 
 NEXTPNR_NAMESPACE_END